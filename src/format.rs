@@ -0,0 +1,342 @@
+//! Share wire-format encodings and a grammar-driven parser.
+//!
+//! A share line begins with a format token which selects a decoder, so the
+//! crate can carry more than one textual representation behind the
+//! [`Format`](enum.Format.html) enum. The legacy `k-index-seq-base64[-crc]`
+//! form is recognised without a token for backwards compatibility; every other
+//! format announces itself with a leading word. Parsing is expressed with a
+//! handful of small combinators so each field reports precisely what failed.
+
+use std::str::FromStr;
+
+use serialize::base64::{ FromBase64, ToBase64 };
+
+use super::{ Share, b64_config, crc24_as_bytes, other_io_err, pie2io };
+
+/// a textual encoding for shares.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+	/// the compact `k-index-seq-base64[-crc]` form
+	Base64,
+	/// a human-transcribable form grouping the data into hex quads, each
+	/// guarded by its own checksum byte
+	Hex,
+}
+
+impl FromStr for Format {
+	type Err = ::std::io::Error;
+
+	fn from_str(s: &str) -> ::std::io::Result<Format> {
+		match s {
+			"base64" | "b64" => Ok(Format::Base64),
+			"hex" => Ok(Format::Hex),
+			other => Err(other_io_err("Unknown share format",
+			                          Some(format!("'{}'", other)))),
+		}
+	}
+}
+
+/// builds a parse error naming the offending field and the reason.
+fn field_err(field: &str, why: &str) -> ::std::io::Error {
+	other_io_err("Share parse error",
+	             Some(format!("field '{}': {}", field, why)))
+}
+
+/// splits a line into its `-`-separated fields, trimming surrounding space.
+fn fields(line: &str) -> Vec<&str> {
+	line.trim().split('-').collect()
+}
+
+/// pops the field at `idx`, failing with a field-specific error when absent.
+fn field<'a>(parts: &[&'a str], idx: usize, name: &'static str) -> ::std::io::Result<&'a str> {
+	parts.get(idx).cloned().ok_or_else(|| field_err(name, "missing"))
+}
+
+/// parses a `u8` field, attaching the field name to any error.
+fn num_u8(parts: &[&str], idx: usize, name: &'static str) -> ::std::io::Result<u8> {
+	try!(field(parts, idx, name)).parse::<u8>().map_err(pie2io)
+}
+
+/// parses a `u32` field, attaching the field name to any error.
+fn num_u32(parts: &[&str], idx: usize, name: &'static str) -> ::std::io::Result<u32> {
+	try!(field(parts, idx, name)).parse::<u32>().map_err(pie2io)
+}
+
+/// auto-detects the format of `line` from its leading token.
+pub fn detect(line: &str) -> Format {
+	let first = line.trim().split('-').next().unwrap_or("");
+	match first {
+		"hex" => Format::Hex,
+		_ => Format::Base64,
+	}
+}
+
+/// parses a share, auto-detecting its format and verifying its checksum.
+pub fn parse(line: &str) -> ::std::io::Result<Share> {
+	parse_with(line, true)
+}
+
+/// parses a share like [`parse`](fn.parse.html) but tolerates a failed
+/// checksum: a CRC-mismatched (e.g. bit-rotted) share is returned with its
+/// `checksum` cleared rather than rejected, so it can still be handed to the
+/// error-correcting decode path.
+pub fn parse_lenient(line: &str) -> ::std::io::Result<Share> {
+	parse_with(line, false)
+}
+
+fn parse_with(line: &str, strict: bool) -> ::std::io::Result<Share> {
+	match detect(line) {
+		Format::Base64 => parse_base64(line, strict),
+		Format::Hex => parse_hex(line, strict),
+	}
+}
+
+/// renders a share in the requested format.
+pub fn render(share: &Share, fmt: Format) -> String {
+	match fmt {
+		Format::Base64 => render_base64(share),
+		Format::Hex => render_hex(share),
+	}
+}
+
+// ---- base64 (default) ----------------------------------------------------
+
+fn parse_base64(line: &str, strict: bool) -> ::std::io::Result<Share> {
+	let mut parts = fields(line);
+	let authenticated = parts.first().map_or(false, |p| *p == "A");
+	if authenticated {
+		parts.remove(0);
+	}
+	if parts.len() < 4 || parts.len() > 5 {
+		return Err(field_err("share", "expected 4 or 5 minus-separated parts"));
+	}
+	let k = try!(num_u8(&parts, 0, "k"));
+	let index = try!(num_u8(&parts, 1, "index"));
+	let seq = try!(num_u32(&parts, 2, "seq"));
+	if k < 1 || index < 1 {
+		return Err(field_err("k/index", "must be positive"));
+	}
+	let data = try!(
+		try!(field(&parts, 3, "data")).from_base64()
+			.map_err(|_| field_err("data", "invalid base64"))
+	);
+	let checksum = try!(parse_crc(parts.get(4).cloned(), k, index, seq, &*data, false, strict));
+	Ok(Share { k: k, index: index, seq: seq, data: data,
+	           checksum: checksum, authenticated: authenticated })
+}
+
+/// parses and verifies the optional CRC-24 field shared by the textual forms.
+///
+/// The trailer is encoded the same way as the share's data: base64 for the
+/// base64 form (`hex == false`), grouped hex for the transcribable hex form
+/// (`hex == true`), so a whole hex line can be written down by hand. When
+/// `strict` is false a mismatched trailer is not fatal: the claimed CRC is kept
+/// as-is so a corrupted share survives parsing *and* the correction path can
+/// still see that it no longer matches its (corrupted) data. A trailer that is
+/// itself malformed (wrong length / bad digits) is dropped (`Ok(None)`).
+fn parse_crc(part: Option<&str>, k: u8, index: u8, seq: u32, data: &[u8], hex: bool, strict: bool)
+             -> ::std::io::Result<Option<[u8; 3]>> {
+	match part {
+		None => Ok(None),
+		Some(check) => {
+			let crc_bytes = if hex {
+				if check.len() != 6 || !check.is_ascii() {
+					return if strict {
+						Err(field_err("crc", "expected six hex digits"))
+					} else {
+						Ok(None)
+					};
+				}
+				let mut v = Vec::with_capacity(3);
+				for bi in 0 .. 3 {
+					match u8::from_str_radix(&check[bi * 2 .. bi * 2 + 2], 16) {
+						Ok(b) => v.push(b),
+						Err(_) if !strict => return Ok(None),
+						Err(_) => return Err(field_err("crc", "non-hex digits")),
+					}
+				}
+				v
+			} else {
+				if check.len() != 4 {
+					return if strict {
+						Err(field_err("crc", "expected four characters"))
+					} else {
+						Ok(None)
+					};
+				}
+				match check.from_base64() {
+					Ok(b) => b,
+					Err(_) if !strict => return Ok(None),
+					Err(_) => return Err(field_err("crc", "invalid base64")),
+				}
+			};
+			if crc_bytes.len() != 3 {
+				return if strict {
+					Err(field_err("crc", "expected three bytes"))
+				} else {
+					Ok(None)
+				};
+			}
+			let mut c = [0u8; 3];
+			c.clone_from_slice(&crc_bytes);
+			if c != crc24_as_bytes(k, index, seq, data) && strict {
+				return Err(field_err("crc", "checksum mismatch"));
+			}
+			// In lenient mode a mismatched CRC is kept, not rejected: the
+			// correction path recomputes it to detect the corrupted share.
+			Ok(Some(c))
+		}
+	}
+}
+
+fn render_base64(share: &Share) -> String {
+	let config = b64_config();
+	let mut out = String::new();
+	if share.authenticated {
+		out.push_str("A-");
+	}
+	out.push_str(&format!("{}-{}-{}-{}", share.k, share.index, share.seq,
+	                      share.data.to_base64(config)));
+	if let Some(ref crc) = share.checksum {
+		out.push_str(&format!("-{}", crc.to_base64(config)));
+	}
+	out
+}
+
+// ---- grouped hex (human-transcribable) -----------------------------------
+
+/// the number of data bytes per hex group.
+const HEX_GROUP: usize = 4;
+
+/// the per-group checksum: a simple additive check byte.
+fn group_checksum(bytes: &[u8]) -> u8 {
+	bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b))
+}
+
+fn parse_hex(line: &str, strict: bool) -> ::std::io::Result<Share> {
+	let mut parts = fields(line);
+	// drop the leading "hex" token
+	if parts.first().map_or(false, |p| *p == "hex") {
+		parts.remove(0);
+	} else {
+		return Err(field_err("format", "expected 'hex' token"));
+	}
+	let authenticated = parts.first().map_or(false, |p| *p == "A");
+	if authenticated {
+		parts.remove(0);
+	}
+	if parts.len() < 4 || parts.len() > 5 {
+		return Err(field_err("share", "expected 4 or 5 minus-separated parts"));
+	}
+	let k = try!(num_u8(&parts, 0, "k"));
+	let index = try!(num_u8(&parts, 1, "index"));
+	let seq = try!(num_u32(&parts, 2, "seq"));
+	if k < 1 || index < 1 {
+		return Err(field_err("k/index", "must be positive"));
+	}
+	let data = try!(decode_hex_groups(try!(field(&parts, 3, "data")), strict));
+	let checksum = try!(parse_crc(parts.get(4).cloned(), k, index, seq, &*data, true, strict));
+	Ok(Share { k: k, index: index, seq: seq, data: data,
+	           checksum: checksum, authenticated: authenticated })
+}
+
+/// decodes space-separated hex groups, verifying the trailing check byte of
+/// each group. Reports which group failed. When `strict` is false a group
+/// whose check byte no longer matches its data is kept anyway, so a bit-rotted
+/// group can still be corrected downstream.
+fn decode_hex_groups(s: &str, strict: bool) -> ::std::io::Result<Vec<u8>> {
+	let mut data = Vec::new();
+	for (gi, group) in s.split(' ').filter(|g| !g.is_empty()).enumerate() {
+		if group.len() % 2 != 0 || group.len() < 2 {
+			return Err(field_err("data", &format!("group {} is not whole bytes", gi)));
+		}
+		let mut bytes = Vec::with_capacity(group.len() / 2);
+		for bi in 0 .. group.len() / 2 {
+			let byte = try!(u8::from_str_radix(&group[bi * 2 .. bi * 2 + 2], 16)
+				.map_err(|_| field_err("data", &format!("group {} has non-hex digits", gi))));
+			bytes.push(byte);
+		}
+		let check = bytes.pop().unwrap();
+		if strict && group_checksum(&*bytes) != check {
+			return Err(field_err("data", &format!("group {} checksum mismatch", gi)));
+		}
+		data.extend(bytes);
+	}
+	Ok(data)
+}
+
+fn render_hex(share: &Share) -> String {
+	let mut out = String::from("hex-");
+	if share.authenticated {
+		out.push_str("A-");
+	}
+	out.push_str(&format!("{}-{}-{}-", share.k, share.index, share.seq));
+	let mut groups = Vec::new();
+	for chunk in share.data.chunks(HEX_GROUP) {
+		let mut g = String::new();
+		for &b in chunk {
+			g.push_str(&format!("{:02x}", b));
+		}
+		g.push_str(&format!("{:02x}", group_checksum(chunk)));
+		groups.push(g);
+	}
+	out.push_str(&groups.join(" "));
+	if let Some(ref crc) = share.checksum {
+		out.push_str(&format!("-{:02x}{:02x}{:02x}", crc[0], crc[1], crc[2]));
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ Format, render, parse, detect };
+	use super::super::{ Share, crc24_as_bytes };
+
+	/// a share with a checksum, used to exercise both encodings.
+	fn sample() -> Share {
+		let data = vec![0x00, 0x7f, 0x80, 0xff, 0x2a, 0x13, 0x41];
+		let checksum = Some(crc24_as_bytes(3, 2, 5, &data));
+		Share { k: 3, index: 2, seq: 5, data: data,
+		        checksum: checksum, authenticated: false }
+	}
+
+	#[test]
+	fn base64_roundtrip() {
+		let share = sample();
+		let line = render(&share, Format::Base64);
+		assert_eq!(detect(&line), Format::Base64);
+		assert_eq!(parse(&line).unwrap(), share);
+	}
+
+	#[test]
+	fn hex_roundtrip() {
+		let share = sample();
+		let line = render(&share, Format::Hex);
+		assert_eq!(detect(&line), Format::Hex);
+		// the whole hex line, crc trailer included, is hex digits only.
+		let trailer = line.rsplit('-').next().unwrap();
+		assert!(trailer.chars().all(|c| c.is_ascii_hexdigit()));
+		assert_eq!(parse(&line).unwrap(), share);
+	}
+
+	#[test]
+	fn authenticated_roundtrips_in_both_formats() {
+		let mut share = sample();
+		share.authenticated = true;
+		for fmt in [Format::Base64, Format::Hex].iter() {
+			let line = render(&share, *fmt);
+			assert_eq!(parse(&line).unwrap(), share);
+		}
+	}
+
+	#[test]
+	fn corrupted_crc_is_rejected() {
+		let line = render(&sample(), Format::Hex);
+		// flip the last hex digit of the crc trailer.
+		let mut bytes: Vec<char> = line.chars().collect();
+		let last = bytes.len() - 1;
+		bytes[last] = if bytes[last] == '0' { '1' } else { '0' };
+		let tampered: String = bytes.into_iter().collect();
+		assert!(parse(&tampered).is_err());
+	}
+}
@@ -0,0 +1,101 @@
+//! Arithmetic in the Galois field GF(2^8).
+//!
+//! Elements are bytes interpreted as polynomials over GF(2) of degree `< 8`.
+//! Addition and subtraction are a bitwise XOR; multiplication is carry-less
+//! multiplication reduced modulo the AES polynomial `x^8 + x^4 + x^3 + x + 1`
+//! (`0x11b`), and the multiplicative inverse is `a^254` by Fermat's little
+//! theorem. This is the field the whole secret-sharing scheme operates in, one
+//! secret byte at a time.
+
+use std::ops::{ Add, Sub, Mul, Div };
+
+/// An element of GF(2^8), stored as the byte of its polynomial coefficients.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Gf256 {
+	/// the raw coefficient byte, low bit first
+	pub poly: u8,
+}
+
+/// carry-less multiply of two field elements, reduced by `0x11b`.
+fn mul_raw(mut a: u8, mut b: u8) -> u8 {
+	let mut prod = 0u8;
+	for _ in 0 .. 8 {
+		if b & 1 != 0 {
+			prod ^= a;
+		}
+		let carry = a & 0x80;
+		a <<= 1;
+		if carry != 0 {
+			a ^= 0x1b; // reduce by x^8 + x^4 + x^3 + x + 1
+		}
+		b >>= 1;
+	}
+	prod
+}
+
+impl Gf256 {
+	/// wraps a raw byte as a field element.
+	pub fn from_byte(b: u8) -> Gf256 {
+		Gf256 { poly: b }
+	}
+
+	/// unwraps the element back into its raw byte.
+	pub fn to_byte(self) -> u8 {
+		self.poly
+	}
+
+	/// the additive identity.
+	pub fn zero() -> Gf256 {
+		Gf256 { poly: 0 }
+	}
+
+	/// the multiplicative identity.
+	pub fn one() -> Gf256 {
+		Gf256 { poly: 1 }
+	}
+
+	/// the multiplicative inverse, computed as `a^254`. The inverse of zero is
+	/// defined as zero so that division never panics; callers divide only by
+	/// known-nonzero elements.
+	pub fn inv(self) -> Gf256 {
+		let mut result = Gf256::one();
+		let mut base = self;
+		let mut exp = 254u32;
+		while exp > 0 {
+			if exp & 1 == 1 {
+				result = result * base;
+			}
+			base = base * base;
+			exp >>= 1;
+		}
+		result
+	}
+}
+
+impl Add for Gf256 {
+	type Output = Gf256;
+	fn add(self, rhs: Gf256) -> Gf256 {
+		Gf256 { poly: self.poly ^ rhs.poly }
+	}
+}
+
+impl Sub for Gf256 {
+	type Output = Gf256;
+	fn sub(self, rhs: Gf256) -> Gf256 {
+		Gf256 { poly: self.poly ^ rhs.poly }
+	}
+}
+
+impl Mul for Gf256 {
+	type Output = Gf256;
+	fn mul(self, rhs: Gf256) -> Gf256 {
+		Gf256 { poly: mul_raw(self.poly, rhs.poly) }
+	}
+}
+
+impl Div for Gf256 {
+	type Output = Gf256;
+	fn div(self, rhs: Gf256) -> Gf256 {
+		self * rhs.inv()
+	}
+}
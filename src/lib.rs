@@ -0,0 +1,863 @@
+//! Shamir's secret sharing scheme as a reusable library.
+//!
+//! The scheme is applied byte-wise within GF(256) for arbitrarily long
+//! secrets. Secrets are processed in fixed-size blocks (see
+//! [`BLOCK_SIZE`](constant.BLOCK_SIZE.html)) so that arbitrarily long inputs
+//! can be streamed with bounded memory: [`encode_stream`](fn.encode_stream.html)
+//! reads a secret from any `Read` and writes shares to any `Write`, and
+//! [`decode_stream`](fn.decode_stream.html) reverses that. The in-memory
+//! [`split`](fn.split.html)/[`combine`](fn.combine.html) pair is a thin
+//! convenience layer over the same machinery.
+//!
+//! `Share` implements `Display`/`FromStr` for the textual
+//! `k-index-seq-base64[-crc]` wire format; the `seq` field frames each block
+//! so a share set can be reassembled in order and truncated or
+//! mismatched-length sets rejected.
+
+// This crate is written in the pre-`?` idiom (`try!`, explicit `&*` derefs,
+// spelled-out struct fields). Rather than churn every original line to satisfy
+// the newer style lints, keep the established idiom and silence those groups
+// crate-wide so `clippy -D warnings` stays clean.
+#![allow(deprecated, bare_trait_objects)]
+#![allow(clippy::redundant_field_names, clippy::explicit_auto_deref,
+         clippy::needless_range_loop, clippy::unnecessary_map_or,
+         clippy::unwrap_or_default, clippy::borrow_deref_ref,
+         clippy::explicit_counter_loop, clippy::question_mark,
+         clippy::op_ref, clippy::manual_repeat_n, clippy::io_other_error,
+         clippy::suspicious_arithmetic_impl)]
+
+extern crate rustc_serialize as serialize;
+extern crate crc24;
+extern crate crypto;
+extern crate rand;
+
+use std::collections::{ BTreeMap, BTreeSet };
+use std::convert;
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::iter::repeat;
+use std::num;
+use std::str::FromStr;
+
+use rand::{ Rng, OsRng };
+use serialize::base64;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+use gf256::Gf256;
+
+pub use format::Format;
+
+mod gf256;
+mod format;
+
+/// the number of secret bytes carried by a single block / share line.
+pub const BLOCK_SIZE: usize = 1024;
+
+/// the length, in bytes, of the truncated SHA-256 tag prepended to the secret
+/// in authenticated mode.
+pub const TAG_LEN: usize = 16;
+
+/// computes the authentication tag (a truncated SHA-256) of `secret`.
+fn secret_tag(secret: &[u8]) -> [u8; TAG_LEN] {
+	let mut h = Sha256::new();
+	h.input(secret);
+	let mut full = [0u8; 32];
+	h.result(&mut full);
+	let mut tag = [0u8; TAG_LEN];
+	tag.clone_from_slice(&full[..TAG_LEN]);
+	tag
+}
+
+fn new_vec<T: Clone>(n: usize, x: T) -> Vec<T> {
+	repeat(x).take(n).collect()
+}
+
+#[derive(Debug)]
+pub struct Error {
+    descr: &'static str,
+    detail: Option<String>,
+}
+
+impl Error {
+    pub fn new(descr: &'static str, detail: Option<String>) -> Error {
+        Error { descr: descr, detail: detail }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.detail {
+            None => write!(f, "{}", self.descr),
+            Some(ref detail) => write!(f, "{} ({})", self.descr, detail)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str { self.descr }
+    fn cause(&self) -> Option<&error::Error> { None }
+}
+
+impl convert::From<Error> for io::Error {
+    fn from(me: Error) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, me)
+    }
+}
+
+/// maps a ParseIntError to an io::Error
+fn pie2io(p: num::ParseIntError) -> io::Error {
+    convert::From::from(
+        Error::new("Integer parsing error", Some(p.to_string()))
+    )
+}
+
+/// constructs an `io::Error` out of a static message and optional detail.
+pub fn other_io_err(descr: &'static str, detail: Option<String>) -> io::Error {
+    convert::From::from(
+        Error::new(descr, detail)
+    )
+}
+
+/// evaluates a polynomial at x=1, 2, 3, ... n (inclusive)
+fn encode<W: Write>(src: &[u8], n: u8, w: &mut W) -> io::Result<()> {
+	for raw_x in 1 .. ((n as u16) + 1) {
+		let x = Gf256::from_byte(raw_x as u8);
+		let mut fac = Gf256::one();
+		let mut acc = Gf256::zero();
+		for &coeff in src.iter() {
+			acc = acc + fac * Gf256::from_byte(coeff);
+			fac = fac * x;
+		}
+		try!(w.write(&[acc.to_byte()]));
+	}
+	Ok(())
+}
+
+/// evaluates an interpolated polynomial at `raw_x` where
+/// the polynomial is determined using Lagrangian interpolation
+/// based on the given x/y coordinates `src`.
+fn lagrange_interpolate(src: &[(u8, u8)], raw_x: u8) -> u8 {
+	let x = Gf256::from_byte(raw_x);
+	let mut sum = Gf256::zero();
+	for (i, &(raw_xi, raw_yi)) in src.iter().enumerate() {
+		let xi = Gf256::from_byte(raw_xi);
+		let yi = Gf256::from_byte(raw_yi);
+		let mut lix = Gf256::one();
+		for (j, &(raw_xj, _)) in src.iter().enumerate() {
+			if i != j {
+				let xj = Gf256::from_byte(raw_xj);
+				let delta = xi - xj;
+				assert!(delta.poly !=0, "Duplicate shares");
+				lix = lix * (x - xj) / delta;
+			}
+		}
+		sum = sum + lix * yi;
+	}
+	sum.to_byte()
+}
+
+/// splits a single block of at most `BLOCK_SIZE` bytes into `n` share columns.
+fn secret_share(src: &[u8], k: u8, n: u8) -> io::Result<Vec<Vec<u8>>> {
+	let mut result = Vec::with_capacity(n as usize);
+	for _ in 0 .. (n as usize) {
+		result.push(new_vec(src.len(), 0u8));
+	}
+	let mut col_in = new_vec(k as usize, 0u8);
+	let mut col_out = Vec::with_capacity(n as usize);
+	let mut osrng = try!(OsRng::new());
+	for (c, &s) in src.iter().enumerate() {
+		col_in[0] = s;
+		osrng.fill_bytes(&mut col_in[1..]);
+		col_out.clear();
+		try!(encode(&*col_in, n, &mut col_out));
+		for (&y, share) in col_out.iter().zip(result.iter_mut()) {
+			share[c] = y;
+		}
+	}
+	Ok(result)
+}
+
+/// computes a CRC-24 hash over the coding parameters k, the share index, the
+/// block sequence number and the raw share data.
+fn crc24_as_bytes(k: u8, index: u8, seq: u32, octets: &[u8]) -> [u8; 3] {
+	use std::hash::Hasher;
+
+	let mut h = crc24::Crc24Hasher::new();
+	h.write(&[k, index]);
+	h.write(&[(seq >> 24) as u8, (seq >> 16) as u8, (seq >> 8) as u8, seq as u8]);
+	h.write(octets);
+	let v = h.finish();
+
+	[((v >> 16) & 0xFF) as u8,
+	 ((v >>  8) & 0xFF) as u8,
+	 ( v        & 0xFF) as u8]
+}
+
+/// the base64 configuration shared by every textual share: standard
+/// alphabet, no padding.
+fn b64_config() -> base64::Config {
+	base64::Config { pad: false, ..base64::STANDARD }
+}
+
+/// A single secret share for one block of the secret.
+///
+/// `index` is the share's x-coordinate (1-based) and `seq` is the 0-based
+/// block sequence number. A complete share carries every block from `seq = 0`
+/// up to and including a final block shorter than `BLOCK_SIZE`, which doubles
+/// as the end-of-stream marker. `checksum` is the optional CRC-24 guarding
+/// transport of this particular block. `authenticated` marks shares whose
+/// secret is prefixed with a SHA-256 tag (see [`TAG_LEN`](constant.TAG_LEN.html));
+/// such shares carry a leading `A` version token so `FromStr` can distinguish
+/// them from legacy shares. The `Display`/`FromStr` pair encodes the
+/// `[A-]k-index-seq-base64[-crc]` wire format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Share {
+	/// the number of shares required to reconstruct the secret
+	pub k: u8,
+	/// the x-coordinate of this share (1-based)
+	pub index: u8,
+	/// the 0-based block sequence number
+	pub seq: u32,
+	/// the share data for this block, one byte per secret byte
+	pub data: Vec<u8>,
+	/// an optional CRC-24 over `(k, index, seq, data)`
+	pub checksum: Option<[u8; 3]>,
+	/// whether the shared secret is prefixed with an authentication tag
+	pub authenticated: bool,
+}
+
+impl fmt::Display for Share {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", format::render(self, Format::Base64))
+	}
+}
+
+impl Share {
+	/// renders this share in the requested textual [`Format`](enum.Format.html).
+	pub fn to_format(&self, fmt: Format) -> String {
+		format::render(self, fmt)
+	}
+}
+
+impl FromStr for Share {
+	type Err = io::Error;
+
+	/// parses a share, auto-detecting its format from the leading token.
+	fn from_str(s: &str) -> io::Result<Share> {
+		format::parse(s)
+	}
+}
+
+/// parses a share like `Share::from_str`, but tolerates a failed checksum: a
+/// CRC-mismatched (e.g. bit-rotted) share is returned with its claimed
+/// `checksum` kept rather than rejected. The error-correcting decode path uses
+/// this so a corrupted share reaches [`combine_with_correction`](fn.combine_with_correction.html)
+/// instead of being rejected at the CRC gate, where the mismatch between the
+/// claimed checksum and the recomputed one flags it as the share to correct.
+pub fn parse_share_lenient(s: &str) -> io::Result<Share> {
+	format::parse_lenient(s)
+}
+
+/// shares one block across `n` columns and turns each column into a `Share`.
+fn share_block(block: &[u8], k: u8, n: u8, seq: u32, with_checksums: bool,
+               authenticated: bool) -> io::Result<Vec<Share>> {
+	let raw = try!(secret_share(block, k, n));
+	let mut shares = Vec::with_capacity(raw.len());
+	for (i, data) in raw.into_iter().enumerate() {
+		let index = (i + 1) as u8;
+		let checksum = if with_checksums {
+			Some(crc24_as_bytes(k, index, seq, &*data))
+		} else {
+			None
+		};
+		shares.push(Share { k: k, index: index, seq: seq, data: data,
+		                    checksum: checksum, authenticated: authenticated });
+	}
+	Ok(shares)
+}
+
+/// reconstructs a single block from `k` or more compatible shares.
+fn reconstruct_block(shares: &[&Share], k: u8) -> Vec<u8> {
+	let slen = shares[0].data.len();
+	let mut col_in = Vec::with_capacity(k as usize);
+	let mut block = Vec::with_capacity(slen);
+	for byteindex in 0 .. slen {
+		col_in.clear();
+		for s in shares.iter().take(k as usize) {
+			col_in.push((s.index, s.data[byteindex]));
+		}
+		block.push(lagrange_interpolate(&*col_in, 0u8));
+	}
+	block
+}
+
+fn split_inner(secret: &[u8], k: u8, n: u8, authenticated: bool) -> io::Result<Vec<Share>> {
+	if !(0 < k && k <= n) {
+		return Err(other_io_err("Invalid encoding parameters K,N", None));
+	}
+	let mut shares = Vec::new();
+	let mut seq = 0u32;
+	let mut pos = 0usize;
+	loop {
+		let end = ::std::cmp::min(pos + BLOCK_SIZE, secret.len());
+		let block = &secret[pos..end];
+		shares.extend(try!(share_block(block, k, n, seq, true, authenticated)));
+		pos = end;
+		seq += 1;
+		// a block shorter than BLOCK_SIZE is the end-of-stream marker; when the
+		// secret length is an exact multiple we still emit a final empty block.
+		if block.len() < BLOCK_SIZE {
+			break;
+		}
+	}
+	Ok(shares)
+}
+
+/// splits `secret` into shares, emitting `n` shares per block.
+///
+/// The result concatenates the shares of every block in order; callers that
+/// want the shares for a single recipient should filter by `index`. Each
+/// returned share carries a CRC-24 checksum over its own block.
+pub fn split(secret: &[u8], k: u8, n: u8) -> io::Result<Vec<Share>> {
+	split_inner(secret, k, n, false)
+}
+
+/// splits `secret` in authenticated mode: a truncated SHA-256 tag is prepended
+/// to the secret before sharing, and every share is marked `authenticated` so
+/// [`combine_authenticated`](fn.combine_authenticated.html) can verify it.
+pub fn split_authenticated(secret: &[u8], k: u8, n: u8) -> io::Result<Vec<Share>> {
+	let mut data = secret_tag(secret).to_vec();
+	data.extend_from_slice(secret);
+	split_inner(&*data, k, n, true)
+}
+
+/// reassembles blocks from `shares` and reconstructs the whole secret.
+///
+/// The shares must agree on `k`; blocks are grouped by `seq` and the first `k`
+/// distinct shares of each block are used. A set missing any block in the
+/// `0..=last` range, or lacking the short terminating block, is rejected as
+/// truncated.
+pub fn combine(shares: &[Share]) -> io::Result<Vec<u8>> {
+	if shares.is_empty() {
+		return Err(other_io_err("No shares provided!", None));
+	}
+	let k = shares[0].k;
+	let mut blocks: BTreeMap<u32, Vec<&Share>> = BTreeMap::new();
+	for s in shares {
+		if s.k != k {
+			return Err(other_io_err("Incompatible shares", None));
+		}
+		let bucket = blocks.entry(s.seq).or_insert_with(Vec::new);
+		if !bucket.is_empty() && bucket[0].data.len() != s.data.len() {
+			return Err(other_io_err("Incompatible shares", None));
+		}
+		if bucket.iter().all(|p| p.index != s.index) {
+			bucket.push(s);
+		}
+	}
+	let mut secret = Vec::new();
+	let mut expected = 0u32;
+	let mut saw_terminator = false;
+	for (&seq, bucket) in blocks.iter() {
+		if seq != expected {
+			return Err(other_io_err("Truncated share set: missing block", None));
+		}
+		if bucket.len() < k as usize {
+			return Err(other_io_err("Not enough shares provided!", None));
+		}
+		let block = reconstruct_block(&*bucket, k);
+		if block.len() < BLOCK_SIZE {
+			saw_terminator = true;
+		}
+		secret.extend(block);
+		expected += 1;
+	}
+	if !saw_terminator {
+		return Err(other_io_err("Truncated share set: missing final block", None));
+	}
+	Ok(secret)
+}
+
+/// solves a linear system over GF(256) by Gaussian elimination.
+///
+/// `rows` is the augmented matrix (each row has `unknowns + 1` entries, the
+/// last being the right-hand side). Returns the unique solution vector, or
+/// `None` if the system is inconsistent or underdetermined.
+fn solve_linear(mut rows: Vec<Vec<Gf256>>, unknowns: usize) -> Option<Vec<Gf256>> {
+	let zero = Gf256::zero();
+	let mut where_col: Vec<Option<usize>> = new_vec(unknowns, None);
+	let mut pivot_row = 0usize;
+	for col in 0 .. unknowns {
+		let sel = (pivot_row .. rows.len()).find(|&r| rows[r][col].to_byte() != 0);
+		if let Some(sel) = sel {
+			rows.swap(pivot_row, sel);
+			let pv = rows[pivot_row][col];
+			for c in col .. unknowns + 1 {
+				rows[pivot_row][c] = rows[pivot_row][c] / pv;
+			}
+			for r in 0 .. rows.len() {
+				if r != pivot_row && rows[r][col].to_byte() != 0 {
+					let factor = rows[r][col];
+					for c in col .. unknowns + 1 {
+						rows[r][c] = rows[r][c] - factor * rows[pivot_row][c];
+					}
+				}
+			}
+			where_col[col] = Some(pivot_row);
+			pivot_row += 1;
+		}
+	}
+	if where_col.iter().any(|w| w.is_none()) {
+		return None; // underdetermined: no unique solution
+	}
+	for r in pivot_row .. rows.len() {
+		if rows[r][unknowns].to_byte() != 0 {
+			return None; // inconsistent
+		}
+	}
+	let mut sol = new_vec(unknowns, zero);
+	for col in 0 .. unknowns {
+		sol[col] = rows[where_col[col].unwrap()][unknowns];
+	}
+	Some(sol)
+}
+
+/// divides the degree-indexed polynomial `num` by the monic-or-not `den`,
+/// returning `(quotient, remainder)`. Coefficients are indexed by degree.
+fn poly_divmod(num: &[Gf256], den: &[Gf256]) -> (Vec<Gf256>, Vec<Gf256>) {
+	let zero = Gf256::zero();
+	let dlead = den.len() - 1;
+	if num.len() < den.len() {
+		return (vec![zero], num.to_vec());
+	}
+	let lead_inv = Gf256::one() / den[dlead];
+	let mut r = num.to_vec();
+	let qlen = r.len() - den.len() + 1;
+	let mut q = new_vec(qlen, zero);
+	for i in (0 .. qlen).rev() {
+		let coeff = r[i + dlead] * lead_inv;
+		q[i] = coeff;
+		for j in 0 .. den.len() {
+			r[i + j] = r[i + j] - coeff * den[j];
+		}
+	}
+	(q, r[0 .. dlead].to_vec())
+}
+
+/// evaluates a degree-indexed polynomial at `raw_x` using Horner's method.
+fn poly_eval(coeffs: &[Gf256], raw_x: u8) -> Gf256 {
+	let x = Gf256::from_byte(raw_x);
+	let mut acc = Gf256::zero();
+	for &c in coeffs.iter().rev() {
+		acc = acc * x + c;
+	}
+	acc
+}
+
+/// Berlekamp-Welch decode of a single byte column tolerating exactly `e`
+/// errors: solves for `Q = E * P` and `E` monic of degree `e`, then recovers
+/// `P = Q / E`. Returns the degree-indexed coefficients of `P` (length `k`),
+/// or `None` if the system has no consistent solution for this `e`.
+fn bw_decode_byte(points: &[(u8, u8)], k: usize, e: usize) -> Option<Vec<Gf256>> {
+	let uq = k + e; // number of Q coefficients
+	let unknowns = uq + e; // plus the e free coefficients of E
+	let mut rows = Vec::with_capacity(points.len());
+	for &(rx, ry) in points {
+		let x = Gf256::from_byte(rx);
+		let y = Gf256::from_byte(ry);
+		let mut row = new_vec(unknowns + 1, Gf256::zero());
+		let mut xp = Gf256::one();
+		for j in 0 .. uq {
+			row[j] = xp;
+			xp = xp * x;
+		}
+		let mut xp2 = Gf256::one();
+		for j in 0 .. e {
+			row[uq + j] = Gf256::zero() - y * xp2;
+			xp2 = xp2 * x;
+		}
+		row[unknowns] = y * xp2; // y * x^e
+		rows.push(row);
+	}
+	let sol = match solve_linear(rows, unknowns) {
+		Some(s) => s,
+		None => return None,
+	};
+	let q = sol[0 .. uq].to_vec();
+	let mut ecoeffs = sol[uq ..].to_vec();
+	ecoeffs.push(Gf256::one()); // E is monic of degree e
+	let (p, rem) = poly_divmod(&q, &ecoeffs);
+	if rem.iter().any(|c| c.to_byte() != 0) {
+		return None; // inconsistent: division left a remainder
+	}
+	Some(p)
+}
+
+/// reconstructs a single block from all `shares`, correcting up to
+/// `(shares.len() - k) / 2` corrupted shares. Corrected share indices are
+/// added to `corrected`.
+fn correct_block(shares: &[&Share], k: u8, corrected: &mut BTreeSet<u8>) -> io::Result<Vec<u8>> {
+	let k = k as usize;
+	let n = shares.len();
+	let emax = (n - k) / 2;
+	let slen = shares[0].data.len();
+	let mut block = Vec::with_capacity(slen);
+	for byteindex in 0 .. slen {
+		let points: Vec<(u8, u8)> =
+			shares.iter().map(|s| (s.index, s.data[byteindex])).collect();
+		let mut solved = None;
+		// start at the maximum correctable error count and retry with fewer;
+		// e = 0 is the plain interpolation fast path.
+		for e in (0 .. emax + 1).rev() {
+			if let Some(p) = bw_decode_byte(&*points, k, e) {
+				solved = Some(p);
+				break;
+			}
+		}
+		let p = try!(solved.ok_or_else(|| other_io_err(
+			"Reconstruction failed — too many corrupted shares", None)));
+		for &(rx, ry) in points.iter() {
+			if poly_eval(&p, rx).to_byte() != ry {
+				corrected.insert(rx);
+			}
+		}
+		block.push(poly_eval(&p, 0).to_byte());
+	}
+	Ok(block)
+}
+
+/// reconstructs the secret from `shares`, correcting corrupted shares via
+/// Berlekamp-Welch decoding when more than `k` shares are supplied.
+///
+/// Returns the reconstructed secret together with the number of distinct
+/// shares that had to be corrected. With exactly `k` shares this degenerates
+/// to plain Lagrange interpolation.
+///
+/// A share whose CRC-24 no longer matches its data is treated as known-corrupt.
+/// If a block carries more such shares than its redundancy can correct (it
+/// needs `k + 2e` shares to fix `e` errors), the whole decode is rejected
+/// rather than silently reconstructed from a bad share.
+pub fn combine_with_correction(shares: &[Share]) -> io::Result<(Vec<u8>, usize)> {
+	if shares.is_empty() {
+		return Err(other_io_err("No shares provided!", None));
+	}
+	let k = shares[0].k;
+	let mut blocks: BTreeMap<u32, Vec<&Share>> = BTreeMap::new();
+	for s in shares {
+		if s.k != k {
+			return Err(other_io_err("Incompatible shares", None));
+		}
+		let bucket = blocks.entry(s.seq).or_insert_with(Vec::new);
+		if !bucket.is_empty() && bucket[0].data.len() != s.data.len() {
+			return Err(other_io_err("Incompatible shares", None));
+		}
+		if bucket.iter().all(|p| p.index != s.index) {
+			bucket.push(s);
+		}
+	}
+	let mut secret = Vec::new();
+	let mut corrected = BTreeSet::new();
+	let mut expected = 0u32;
+	let mut saw_terminator = false;
+	for (&seq, bucket) in blocks.iter() {
+		if seq != expected {
+			return Err(other_io_err("Truncated share set: missing block", None));
+		}
+		if bucket.len() < k as usize {
+			return Err(other_io_err("Not enough shares provided!", None));
+		}
+		// Shares whose stored CRC no longer matches their data are known to be
+		// corrupt. Correcting `bad` of them needs `k + 2*bad` shares; with fewer
+		// the plain-interpolation branch below would silently fold a bad share
+		// into the output, so reject loudly instead.
+		let bad = bucket.iter().filter(|s|
+			s.checksum.map_or(false, |cs| cs != crc24_as_bytes(s.k, s.index, s.seq, &*s.data))
+		).count();
+		if bad > 0 && bucket.len() < k as usize + 2 * bad {
+			return Err(other_io_err(
+				"Not enough redundant shares to correct the corrupted shares", None));
+		}
+		let block = if bucket.len() == k as usize {
+			reconstruct_block(&*bucket, k)
+		} else {
+			try!(correct_block(&*bucket, k, &mut corrected))
+		};
+		if block.len() < BLOCK_SIZE {
+			saw_terminator = true;
+		}
+		secret.extend(block);
+		expected += 1;
+	}
+	if !saw_terminator {
+		return Err(other_io_err("Truncated share set: missing final block", None));
+	}
+	Ok((secret, corrected.len()))
+}
+
+/// reconstructs and authenticates the secret from `shares`.
+///
+/// After reconstruction the prepended tag is recomputed and compared; on a
+/// mismatch the secret is rejected rather than returned. Returns the verified
+/// secret (with the tag stripped) and the number of shares corrected along the
+/// way.
+pub fn combine_authenticated(shares: &[Share]) -> io::Result<(Vec<u8>, usize)> {
+	let (data, corrected) = try!(combine_with_correction(shares));
+	if data.len() < TAG_LEN {
+		return Err(other_io_err(
+			"Reconstruction failed — wrong or corrupted shares", None));
+	}
+	let (tag, secret) = data.split_at(TAG_LEN);
+	if tag != &secret_tag(secret)[..] {
+		return Err(other_io_err(
+			"Reconstruction failed — wrong or corrupted shares", None));
+	}
+	Ok((secret.to_vec(), corrected))
+}
+
+/// streams a secret from `src`, writing one share line per block and column to
+/// `dst`. In plain mode memory use is bounded to a single block regardless of
+/// secret length; in authenticated mode the secret is buffered so its tag can
+/// be prepended before sharing.
+pub fn encode_stream<R: Read, W: Write>(src: &mut R, k: u8, n: u8,
+                                        with_checksums: bool, authenticated: bool,
+                                        format: Format, dst: &mut W) -> io::Result<()> {
+	if !(0 < k && k <= n) {
+		return Err(other_io_err("Invalid encoding parameters K,N", None));
+	}
+	if authenticated {
+		let mut secret = Vec::new();
+		try!(src.read_to_end(&mut secret));
+		for share in try!(split_authenticated(&*secret, k, n)) {
+			let share = if with_checksums { share } else {
+				Share { checksum: None, ..share }
+			};
+			try!(writeln!(dst, "{}", share.to_format(format)));
+		}
+		return dst.flush();
+	}
+	let mut buf = new_vec(BLOCK_SIZE, 0u8);
+	let mut seq = 0u32;
+	loop {
+		let mut filled = 0usize;
+		while filled < BLOCK_SIZE {
+			let got = try!(src.read(&mut buf[filled..]));
+			if got == 0 {
+				break;
+			}
+			filled += got;
+		}
+		for share in try!(share_block(&buf[..filled], k, n, seq, with_checksums, false)) {
+			try!(writeln!(dst, "{}", share.to_format(format)));
+		}
+		seq += 1;
+		if filled < BLOCK_SIZE {
+			break;
+		}
+	}
+	dst.flush()
+}
+
+/// streams share lines from `src`, reconstructing the secret to `dst` as each
+/// block completes. Blocks are emitted in `seq` order; out-of-order input is
+/// buffered until the next expected block is ready.
+pub fn decode_stream<R: BufRead, W: Write>(src: &mut R, dst: &mut W) -> io::Result<()> {
+	let mut pending: BTreeMap<u32, Vec<Share>> = BTreeMap::new();
+	let mut k: Option<u8> = None;
+	let mut next = 0u32;
+	let mut done = false;
+	for line in src.lines() {
+		let line = try!(line);
+		if line.trim().is_empty() {
+			continue;
+		}
+		let share: Share = try!(line.parse());
+		match k {
+			Some(ck) if ck != share.k =>
+				return Err(other_io_err("Incompatible shares", None)),
+			Some(_) => {},
+			None => k = Some(share.k),
+		}
+		let bucket = pending.entry(share.seq).or_insert_with(Vec::new);
+		if bucket.iter().all(|s| s.index != share.index) {
+			bucket.push(share);
+		}
+		let need = k.unwrap() as usize;
+		while pending.get(&next).map_or(false, |b| b.len() >= need) {
+			let bucket = pending.remove(&next).unwrap();
+			let refs: Vec<&Share> = bucket.iter().collect();
+			let block = reconstruct_block(&*refs, k.unwrap());
+			try!(dst.write_all(&*block));
+			next += 1;
+			if block.len() < BLOCK_SIZE {
+				done = true;
+				break;
+			}
+		}
+		if done {
+			break;
+		}
+	}
+	if !done {
+		return Err(other_io_err("Truncated share set: missing final block", None));
+	}
+	dst.flush()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::{ poly_divmod, correct_block };
+	use super::gf256::Gf256;
+	use std::collections::BTreeSet;
+
+	/// wraps raw bytes as field elements for the polynomial helpers.
+	fn gf(bytes: &[u8]) -> Vec<Gf256> {
+		bytes.iter().map(|&b| Gf256::from_byte(b)).collect()
+	}
+
+	/// flips every data byte of the shares with the given `index`, simulating a
+	/// fully corrupted share.
+	fn corrupt(shares: &mut [Share], index: u8) {
+		for s in shares.iter_mut().filter(|s| s.index == index) {
+			for b in s.data.iter_mut() {
+				*b ^= 0xff;
+			}
+		}
+	}
+
+	#[test]
+	fn split_combine_roundtrip() {
+		let secret = b"the quick brown fox jumps over the lazy dog";
+		let shares = split(secret, 3, 5).unwrap();
+		assert_eq!(combine(&shares).unwrap(), &secret[..]);
+	}
+
+	#[test]
+	fn combine_from_exactly_k_shares() {
+		let secret = b"attack at dawn";
+		let shares = split(secret, 3, 5).unwrap();
+		let subset: Vec<Share> = shares.into_iter()
+			.filter(|s| s.index == 1 || s.index == 2 || s.index == 4)
+			.collect();
+		assert_eq!(combine(&subset).unwrap(), &secret[..]);
+	}
+
+	#[test]
+	fn split_combine_multi_block() {
+		let secret: Vec<u8> = (0 .. (BLOCK_SIZE * 2 + 37)).map(|i| i as u8).collect();
+		let shares = split(&secret, 2, 3).unwrap();
+		assert_eq!(combine(&shares).unwrap(), secret);
+	}
+
+	#[test]
+	fn correction_fixes_single_error() {
+		let secret = b"correct horse battery staple";
+		let mut shares = split(secret, 2, 5).unwrap();
+		corrupt(&mut shares, 3);
+		let (recovered, corrected) = combine_with_correction(&shares).unwrap();
+		assert_eq!(recovered, &secret[..]);
+		assert_eq!(corrected, 1);
+	}
+
+	#[test]
+	fn correction_through_textual_parse() {
+		use std::str::FromStr;
+		// the CLI path renders shares to text and parses them back, so exercise
+		// correction through that gate: flip a byte of one share's base64 data
+		// without touching its CRC, exactly as on-disk bit-rot would.
+		let secret = b"bit-rot happens to real backups";
+		let shares = split(secret, 2, 5).unwrap();
+		let mut lines: Vec<String> = shares.iter().map(|s| s.to_string()).collect();
+		let target = shares.iter().position(|s| s.index == 3).unwrap();
+		{
+			let mut tok: Vec<String> = lines[target].split('-').map(String::from).collect();
+			let mut d: Vec<char> = tok[3].chars().collect();
+			d[0] = if d[0] == 'A' { 'B' } else { 'A' };
+			tok[3] = d.into_iter().collect();
+			lines[target] = tok.join("-");
+		}
+		// the strict parser rejects the corrupted share at the CRC gate ...
+		assert!(Share::from_str(&lines[target]).is_err());
+		// ... but the lenient parser keeps it so Berlekamp-Welch can fix it.
+		let parsed: Vec<Share> = lines.iter()
+			.map(|l| parse_share_lenient(l).unwrap())
+			.collect();
+		let (recovered, corrected) = combine_with_correction(&parsed).unwrap();
+		assert_eq!(recovered, &secret[..]);
+		assert_eq!(corrected, 1);
+	}
+
+	#[test]
+	fn correction_at_error_boundary() {
+		// with n = 6, k = 2 the code tolerates exactly (6 - 2) / 2 = 2 errors.
+		let secret = b"boundary case secret";
+		let mut shares = split(secret, 2, 6).unwrap();
+		corrupt(&mut shares, 3);
+		corrupt(&mut shares, 5);
+		let (recovered, corrected) = combine_with_correction(&shares).unwrap();
+		assert_eq!(recovered, &secret[..]);
+		assert_eq!(corrected, 2);
+	}
+
+	#[test]
+	fn correction_rejects_too_many_errors() {
+		// n = 4, k = 2 tolerates one error; corrupting three is beyond the
+		// correction bound. The shares carry checksums, so the three corrupted
+		// ones are recognised as such and the decode is rejected outright rather
+		// than silently passing off garbage as the original secret.
+		let secret = b"too many errors";
+		let mut shares = split(secret, 2, 4).unwrap();
+		corrupt(&mut shares, 2);
+		corrupt(&mut shares, 3);
+		corrupt(&mut shares, 4);
+		assert!(combine_with_correction(&shares).is_err());
+	}
+
+	#[test]
+	fn poly_divmod_exact_and_remainder() {
+		// (1 + x)(3 + x) = 3 + 2x + x^2 over GF(256), so dividing back is exact.
+		let (q, r) = poly_divmod(&gf(&[3, 2, 1]), &gf(&[1, 1]));
+		assert_eq!(q, gf(&[3, 1]));
+		assert_eq!(r, gf(&[0]));
+		// x^2 is not divisible by (1 + x): a nonzero remainder is left.
+		let (_, r) = poly_divmod(&gf(&[0, 0, 1]), &gf(&[1, 1]));
+		assert!(r.iter().any(|c| c.to_byte() != 0));
+	}
+
+	#[test]
+	fn correct_block_counts_distinct_shares() {
+		let secret = b"xyz";
+		let mut shares = split(secret, 2, 5).unwrap();
+		corrupt(&mut shares, 4);
+		let refs: Vec<&Share> = shares.iter().collect();
+		let mut corrected = BTreeSet::new();
+		let block = correct_block(&refs, 2, &mut corrected).unwrap();
+		assert_eq!(&block[..secret.len()], &secret[..]);
+		assert_eq!(corrected.into_iter().collect::<Vec<_>>(), vec![4]);
+	}
+
+	#[test]
+	fn authenticated_roundtrip_accepts() {
+		let secret = b"authenticated secret payload";
+		let shares = split_authenticated(secret, 2, 3).unwrap();
+		let (recovered, corrected) = combine_authenticated(&shares).unwrap();
+		assert_eq!(recovered, &secret[..]);
+		assert_eq!(corrected, 0);
+	}
+
+	#[test]
+	fn authenticated_rejects_tampered_share() {
+		let secret = b"authenticated secret payload";
+		let shares = split_authenticated(secret, 2, 3).unwrap();
+		// keep exactly k shares so there is no redundancy to correct with, then
+		// tamper one: reconstruction yields garbage and the tag must reject it.
+		let mut subset: Vec<Share> = shares.into_iter()
+			.filter(|s| s.index == 1 || s.index == 2)
+			.collect();
+		corrupt(&mut subset, 2);
+		assert!(combine_authenticated(&subset).is_err());
+	}
+}
@@ -1,286 +1,129 @@
-extern crate rustc_serialize as serialize;
+// See the note in `lib.rs`: keep the crate's original pre-`?` idiom and silence
+// the newer style lints rather than rewrite every line.
+#![allow(deprecated)]
+#![allow(clippy::redundant_field_names, clippy::explicit_auto_deref,
+         clippy::needless_range_loop, clippy::unnecessary_map_or,
+         clippy::unwrap_or_default, clippy::borrow_deref_ref,
+         clippy::explicit_counter_loop, clippy::question_mark,
+         clippy::op_ref, clippy::manual_repeat_n, clippy::io_other_error)]
+
 extern crate getopts;
-extern crate crc24;
-extern crate rand;
+extern crate secretshare;
 
-use std::convert;
 use std::env;
-use std::error;
-use std::fmt;
 use std::io;
 use std::io::prelude::*;
-use std::iter::repeat;
-use std::num;
 
-use rand::{ Rng, OsRng };
 use getopts::Options;
-use serialize::base64::{ self, FromBase64, ToBase64 };
-
-use gf256::Gf256;
-
-mod gf256;
-
-fn new_vec<T: Clone>(n: usize, x: T) -> Vec<T> {
-	repeat(x).take(n).collect()
-}
-
-#[derive(Debug)]
-pub struct Error {
-    descr: &'static str,
-    detail: Option<String>,
-}
-
-impl Error {
-    fn new(descr: &'static str, detail: Option<String>) -> Error {
-        Error { descr: descr, detail: detail }
-    }
-}
-
-impl fmt::Display for Error {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.detail {
-            None => write!(f, "{}", self.descr),
-            Some(ref detail) => write!(f, "{} ({})", self.descr, detail)
-        }
-    }
-}
-
-impl error::Error for Error {
-    fn description(&self) -> &str { self.descr }
-    fn cause(&self) -> Option<&error::Error> { None }
-}
-
-impl convert::From<Error> for io::Error {
-    fn from(me: Error) -> io::Error {
-        io::Error::new(io::ErrorKind::Other, me)
-    }
-}
-
-// a try!-like macro for Option<T> expressions that takes
-// a &'static str as error message as 2nd parameter
-// and creates an Error out of it if necessary.
-macro_rules! otry {
-	($o:expr, $e:expr) => (
-		match $o {
-			Some(thing_) => thing_,
-			None => return Err(convert::From::from(Error::new($e, None)))
-		}
-	)
-}
-
-/// maps a ParseIntError to an io::Error
-fn pie2io(p: num::ParseIntError) -> io::Error {
-    convert::From::from(
-        Error::new("Integer parsing error", Some(p.to_string()))
-    )
-}
-
-fn other_io_err(descr: &'static str, detail: Option<String>) -> io::Error {
-    convert::From::from(
-        Error::new(descr, detail)
-    )
-}
 
-/// evaluates a polynomial at x=1, 2, 3, ... n (inclusive)
-fn encode<W: Write>(src: &[u8], n: u8, w: &mut W) -> io::Result<()> {
-	for raw_x in 1 .. ((n as u16) + 1) {
-		let x = Gf256::from_byte(raw_x as u8);
-		let mut fac = Gf256::one();
-		let mut acc = Gf256::zero();
-		for &coeff in src.iter() {
-			acc = acc + fac * Gf256::from_byte(coeff);
-			fac = fac * x;
-		}
-		try!(w.write(&[acc.to_byte()]));
-	}
-	Ok(())
-}
-
-/// evaluates an interpolated polynomial at `raw_x` where
-/// the polynomial is determined using Lagrangian interpolation
-/// based on the given x/y coordinates `src`.
-fn lagrange_interpolate(src: &[(u8, u8)], raw_x: u8) -> u8 {
-	let x = Gf256::from_byte(raw_x);
-	let mut sum = Gf256::zero();
-	for (i, &(raw_xi, raw_yi)) in src.iter().enumerate() {
-		let xi = Gf256::from_byte(raw_xi);
-		let yi = Gf256::from_byte(raw_yi);
-		let mut lix = Gf256::one();
-		for (j, &(raw_xj, _)) in src.iter().enumerate() {
-			if i != j {
-				let xj = Gf256::from_byte(raw_xj);
-				let delta = xi - xj;
-				assert!(delta.poly !=0, "Duplicate shares");
-				lix = lix * (x - xj) / delta;
-			}
-		}
-		sum = sum + lix * yi;
-	}
-	sum.to_byte()
-}
-
-fn secret_share(src: &[u8], k: u8, n: u8) -> io::Result<Vec<Vec<u8>>> {
-	let mut result = Vec::with_capacity(n as usize);
-	for _ in 0 .. (n as usize) {
-		result.push(new_vec(src.len(), 0u8));
-	}
-	let mut col_in = new_vec(k as usize, 0u8);
-	let mut col_out = Vec::with_capacity(n as usize);
-	let mut osrng = try!(OsRng::new());
-	for (c, &s) in src.iter().enumerate() {
-		col_in[0] = s;
-		osrng.fill_bytes(&mut col_in[1..]);
-		col_out.clear();
-		try!(encode(&*col_in, n, &mut col_out));
-		for (&y, share) in col_out.iter().zip(result.iter_mut()) {
-			share[c] = y;
-		}
-	}
-	Ok(result)
-}
+use secretshare::{ Share, Format, encode_stream, decode_stream,
+                   combine_with_correction, combine_authenticated,
+                   parse_share_lenient, other_io_err };
 
 enum Action {
-	Encode(u8, u8), // k and n parameter
-	Decode
+	Encode(u8, u8, bool, Format), // k, n, authenticated-mode flag and output format
+	Decode(bool) // whether to buffer every share and correct corrupted ones
 }
 
 fn parse_k_n(s: &str) -> io::Result<(u8, u8)> {
 	let mut iter = s.split(',');
 	let msg = "K and N have to be separated with a comma";
-	let s1 = otry!(iter.next(), msg).trim();
-	let s2 = otry!(iter.next(), msg).trim();
-	let k = try!(s1.parse().map_err(pie2io));
-	let n = try!(s2.parse().map_err(pie2io));
+	let s1 = try!(iter.next().ok_or_else(|| other_io_err(msg, None))).trim();
+	let s2 = try!(iter.next().ok_or_else(|| other_io_err(msg, None))).trim();
+	let k = try!(s1.parse().map_err(|_| other_io_err("Could not parse K parameter", None)));
+	let n = try!(s2.parse().map_err(|_| other_io_err("Could not parse N parameter", None)));
 	Ok((k, n))
 }
 
-/// computes a CRC-24 hash over the concatenated coding parameters k, n
-/// and the raw share data
-fn crc24_as_bytes(k: u8, n: u8, octets: &[u8]) -> [u8; 3] {
-	use std::hash::Hasher;
-
-	let mut h = crc24::Crc24Hasher::new();
-	h.write(&[k, n]);
-	h.write(octets);
-	let v = h.finish();
-
-	[((v >> 16) & 0xFF) as u8,
-	 ((v >>  8) & 0xFF) as u8,
-	 ( v        & 0xFF) as u8]
-}
-
-fn perform_encode(k: u8, n: u8, with_checksums: bool) -> io::Result<()> {
-    let secret = {
-        let limit: usize = 0x10000;
-        let stdin = io::stdin();
-        let mut locked = stdin.lock();
-        let mut tmp: Vec<u8> = Vec::new();
-        try!(locked.by_ref().take(limit as u64).read_to_end(&mut tmp));
-        if tmp.len() == limit {
-            let mut dummy = [0u8];
-            if try!(locked.read(&mut dummy)) > 0 {
-                return Err(other_io_err("Secret too large",
-                                        Some(format!("My limit is at {} bytes.", limit))));
-            }
-        }
-        tmp
-    };
-	let shares = try!(secret_share(&*secret, k, n));
-	let config = base64::Config {
-		pad: false,
-		..base64::STANDARD
-	};
-	for (index, share) in shares.iter().enumerate() {
-		let salad = share.to_base64(config);
-		if with_checksums {
-			let crc_bytes = crc24_as_bytes(k, (index+1) as u8, &**share);
-			println!("{}-{}-{}-{}", k, index+1, salad, crc_bytes.to_base64(config));
-		} else {
-			println!("{}-{}-{}", k, index+1, salad);
+fn perform_encode(k: u8, n: u8, with_checksums: bool, authenticated: bool,
+                  format: Format) -> io::Result<()> {
+	let stdin = io::stdin();
+	let mut input = stdin.lock();
+	let stdout = io::stdout();
+	let mut output = stdout.lock();
+	encode_stream(&mut input, k, n, with_checksums, authenticated, format, &mut output)
+}
+
+fn perform_decode(correct: bool) -> io::Result<()> {
+	let stdin = io::stdin();
+	let mut input = io::BufReader::new(stdin.lock());
+	let stdout = io::stdout();
+	let mut output = stdout.lock();
+
+	// Peek the first non-empty line: it tells us whether the shares are
+	// authenticated. Authenticated reconstruction needs the whole secret in
+	// memory to recompute its tag, and error correction needs every share of a
+	// block at once, so both of those paths buffer the full set. The common
+	// plain, non-correcting path instead streams block by block through
+	// `decode_stream`, keeping decode memory bounded regardless of how long the
+	// secret is.
+	//
+	// Note the intended deviation from "use all provided shares by default":
+	// without `-c` the plain path reconstructs each block from the first `k`
+	// shares via Lagrange interpolation and does *not* attempt correction, so a
+	// CRC-consistent bad share among those `k` yields wrong output. Correction
+	// needs the whole share set buffered (incompatible with the streaming,
+	// bounded-memory default), so it is opt-in via `-c`.
+	let mut first = String::new();
+	loop {
+		first.clear();
+		if try!(input.read_line(&mut first)) == 0 {
+			return Err(other_io_err("Not enough shares provided!", None));
 		}
-	}
-	Ok(())
-}
-
-/// reads shares from stdin and returns Ok(k, shares) on success
-/// where shares is a Vec<(u8, Vec<u8>)> representing x-coordinates
-/// and share data.
-fn read_shares() -> io::Result<(u8, Vec<(u8,Vec<u8>)>)> {
-    let stdin = io::stdin();
-	let stdin = io::BufReader::new(stdin.lock());
-	let mut opt_k_l: Option<(u8, usize)> = None;
-	let mut counter = 0u8;
-	let mut shares: Vec<(u8,Vec<u8>)> = Vec::new();
-	for line in stdin.lines() {
-		let line = try!(line);
-		let parts: Vec<_> = line.trim().split('-').collect();
-		if parts.len() < 3 || parts.len() > 4 {
-			return Err(other_io_err("Share parse error: Expected 3 or 4 \
-			                         parts searated by a minus sign", None));
+		if !first.trim().is_empty() {
+			break;
 		}
-		let (k, n, p3, opt_p4) = {
-			let mut iter = parts.into_iter();
-			let k = try!(iter.next().unwrap().parse::<u8>().map_err(pie2io));
-			let n = try!(iter.next().unwrap().parse::<u8>().map_err(pie2io));
-			let p3 = iter.next().unwrap();
-			let opt_p4 = iter.next();
-			(k, n, p3, opt_p4)
-		};
-		if k < 1 || n < 1 {
-			return Err(other_io_err("Share parse error: Illegal K,N parameters", None));
+	}
+	// parse leniently: the probe (and, below, every buffered share) may be a
+	// bit-rotted share whose CRC no longer matches. On the correcting path such
+	// a share must survive parsing so Berlekamp-Welch can fix it; the plain path
+	// re-parses strictly inside `decode_stream`.
+	let probe = parse_share_lenient(&first);
+	let authenticated = probe.as_ref().map(|s| s.authenticated).unwrap_or(false);
+
+	if authenticated || correct {
+		let mut shares: Vec<Share> = Vec::new();
+		let mut dropped = 0usize;
+		// the probe gets the same drop-on-failure treatment as the rest: a
+		// garbled first line must not abort a decode the redundant shares could
+		// still satisfy.
+		match probe {
+			Ok(share) => shares.push(share),
+			Err(_) => dropped += 1,
 		}
-		let data = try!(
-			p3.from_base64().map_err(|_| other_io_err(
-				"Share parse error: Base64 decoding of data block failed", None ))
-		);
-		if let Some(check) = opt_p4 {
-			if check.len() != 4 {
-				return Err(other_io_err("Share parse error: Checksum part is \
-				                         expected to be four characters", None));
+		for line in input.lines() {
+			let line = try!(line);
+			if line.trim().is_empty() {
+				continue;
 			}
-			let crc_bytes = try!(
-				check.from_base64().map_err(|_| other_io_err(
-					"Share parse error: Base64 decoding of checksum failed", None))
-			);
-			let mychksum = crc24_as_bytes(k, n, &*data);
-			if crc_bytes != mychksum {
-				return Err(other_io_err("Share parse error: Checksum mismatch", None));
+			// a line too garbled to parse is dropped rather than aborting the
+			// whole decode: the redundant shares can still reconstruct the secret.
+			match parse_share_lenient(&line) {
+				Ok(share) => shares.push(share),
+				Err(_) => dropped += 1,
 			}
 		}
-		if let Some((ck, cl)) = opt_k_l {
-			if ck != k || cl != data.len() {
-				return Err(other_io_err("Incompatible shares", None));
-			}
-		} else {
-			opt_k_l = Some((k, data.len()));
+		if dropped > 0 {
+			drop(writeln!(&mut io::stderr(), "Dropped {} unparseable share(s).", dropped));
 		}
-		if shares.iter().all(|s| s.0 != n) {
-			shares.push((n, data));
-			counter += 1;
-			if counter == k {
-				return Ok((k, shares));
-			}
+		// use every share provided so redundant shares can correct corrupted
+		// ones; authenticated shares additionally have their tag verified.
+		let (secret, corrected) = if shares.first().map_or(false, |s| s.authenticated) {
+			try!(combine_authenticated(&*shares))
+		} else {
+			try!(combine_with_correction(&*shares))
+		};
+		if corrected > 0 {
+			drop(writeln!(&mut io::stderr(), "Corrected {} corrupted share(s).", corrected));
 		}
+		try!(output.write_all(&*secret));
+		return output.flush();
 	}
-	Err(other_io_err("Not enough shares provided!", None))
-}
 
-fn perform_decode() -> io::Result<()> {
-	let (k, shares) = try!(read_shares());
-	assert!(!shares.is_empty());
-	let slen = shares[0].1.len();
-	let mut col_in = Vec::with_capacity(k as usize);
-	let mut secret = Vec::with_capacity(slen);
-	for byteindex in 0 .. slen {
-		col_in.clear();
-		for s in shares.iter().take(k as usize) {
-			col_in.push((s.0, s.1[byteindex]));
-		}
-		secret.push(lagrange_interpolate(&*col_in, 0u8));
-	}
-	let mut out = io::stdout();
-	try!(out.write_all(&*secret));
-	out.flush()
+	// plain path: put the peeked line back in front of the rest of stdin and
+	// stream the reconstruction.
+	let mut rest = io::BufReader::new(io::Cursor::new(first).chain(input));
+	decode_stream(&mut rest, &mut output)
 }
 
 fn main() {
@@ -290,9 +133,22 @@ fn main() {
 	let mut opts = Options::new();
 	opts.optflag("h", "help", "print this help text");
 	opts.optflag("d", "decode", "for decoding");
+	opts.optflag("c", "correct", "when decoding, buffer every share and use the \
+	                              redundant ones to correct corrupted shares, \
+	                              including bit-rotted shares whose CRC no longer \
+	                              matches (uses memory proportional to the \
+	                              secret). Without this flag decode streams and \
+	                              uses only the first K shares per block, without \
+	                              correction.");
 	opts.optopt("e", "encode", "for encoding, K is the required number of \
 	                            shares for decoding, N is the number of shares \
 	                            to generate. 1 <= K <= N <= 255", "K,N");
+	opts.optflag("a", "auth", "authenticated encoding: embed a keyed digest of \
+	                           the secret so a wrong or corrupted reconstruction \
+	                           is detected and rejected");
+	opts.optopt("f", "format", "output share encoding when encoding: 'base64' \
+	                            (default) or 'hex' (human-transcribable). The \
+	                            format is auto-detected on decode.", "FMT");
 	let opt_matches = match opts.parse(&args[1..]) {
 		Ok(m) => m,
 		Err(f) => {
@@ -311,16 +167,24 @@ fn main() {
  		return;
 	}
 
-	let action: Result<_,_> = 
+	let action: Result<_,_> =
 		match (opt_matches.opt_present("e"), opt_matches.opt_present("d")) {
 			(false, false) => Err("Nothing to do! Use -e or -d"),
 			(true, true) => Err("Use either -e or -d and not both"),
-			(false, true) => Ok(Action::Decode),
+			(false, true) => Ok(Action::Decode(opt_matches.opt_present("c"))),
 			(true, false) => {
 				if let Some(param) = opt_matches.opt_str("e") {
 					if let Ok((k,n)) = parse_k_n(&*param) {
 						if 0 < k && k <= n {
-							Ok(Action::Encode(k,n))
+							let format = match opt_matches.opt_str("f") {
+								Some(ref f) => f.parse(),
+								None => Ok(Format::Base64),
+							};
+							match format {
+								Ok(format) =>
+									Ok(Action::Encode(k, n, opt_matches.opt_present("a"), format)),
+								Err(_) => Err("Unknown share format for --format"),
+							}
 						} else {
 							Err("Invalid encoding parameters K,N")
 						}
@@ -335,8 +199,8 @@ fn main() {
 
 	let result =
 		match action {
-			Ok(Action::Encode(k,n)) => perform_encode(k, n, true),
-			Ok(Action::Decode) => perform_decode(),
+			Ok(Action::Encode(k,n,auth,fmt)) => perform_encode(k, n, true, auth, fmt),
+			Ok(Action::Decode(correct)) => perform_decode(correct),
 			Err(e) => Err(other_io_err(e, None))
 		};
 
@@ -345,4 +209,3 @@ fn main() {
 		// env::set_exit_status(1); // FIXME: unstable feature
 	}
 }
-